@@ -0,0 +1,13 @@
+//! Synchronization and interior mutability primitives
+
+mod condvar;
+mod mutex;
+mod rwlock;
+mod semaphore;
+mod up;
+
+pub use condvar::Condvar;
+pub use mutex::{Mutex, MutexBlocking, MutexSpin, UnlockOutcome};
+pub use rwlock::RwLock;
+pub use semaphore::Semaphore;
+pub use up::UPSafeCell;
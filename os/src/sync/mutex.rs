@@ -0,0 +1,206 @@
+use super::UPSafeCell;
+use crate::task::{add_task, block_current_and_run_next, current_task, suspend_current_and_run_next, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// What releasing a `Mutex` actually did, so the caller can tell a real
+/// release apart from a direct fair-mode hand-off.
+pub enum UnlockOutcome {
+    /// The mutex is genuinely free; nobody holds it.
+    Free,
+    /// Handed straight to this `Arc<TaskControlBlock>` (a fair-mode
+    /// queue-head hand-off); the mutex is still logically locked, on that
+    /// task's behalf, until it wakes and claims it.
+    HandedOff(Arc<TaskControlBlock>),
+}
+
+pub trait Mutex: Sync + Send {
+    fn lock(&self);
+
+    /// Release the mutex. See `UnlockOutcome`.
+    fn unlock(&self) -> UnlockOutcome;
+
+    /// Block until granted, or until woken by the timer the caller armed
+    /// just before this call, whichever happens first. Returns whether
+    /// the mutex was actually acquired.
+    ///
+    /// The default falls back to an untimed `lock`: a spinlock has no
+    /// wait queue to remove a timed-out waiter from, so `MutexSpin` is
+    /// not a meaningful target for the timed syscalls. `MutexBlocking`
+    /// overrides this with a real timeout race.
+    fn lock_timeout(&self) -> bool {
+        self.lock();
+        true
+    }
+}
+
+pub struct MutexSpin {
+    locked: UPSafeCell<bool>,
+}
+
+impl MutexSpin {
+    pub fn new() -> Self {
+        Self {
+            locked: unsafe { UPSafeCell::new(false) },
+        }
+    }
+}
+
+impl Mutex for MutexSpin {
+    fn lock(&self) {
+        loop {
+            let mut locked = self.locked.exclusive_access();
+            if *locked {
+                drop(locked);
+                suspend_current_and_run_next();
+                continue;
+            } else {
+                *locked = true;
+                return;
+            }
+        }
+    }
+
+    fn unlock(&self) -> UnlockOutcome {
+        let mut locked = self.locked.exclusive_access();
+        *locked = false;
+        UnlockOutcome::Free
+    }
+}
+
+/// A blocking mutex with a selectable fairness mode.
+///
+/// `fair == true` gives a bounded-waiting/FIFO handoff: waiters join a
+/// strict FIFO queue and `unlock` hands the mutex directly to the queue
+/// head (`locked` stays `true` throughout the handoff), so no newly
+/// arriving thread can barge ahead of an already-queued one.
+///
+/// `fair == false` (the default, existing fast-path) releases the lock
+/// fully on `unlock` and only wakes a waiter as a hint to retry: the woken
+/// task loops back and re-checks `locked`, so a fresh contender that calls
+/// `lock` first can still win the race.
+pub struct MutexBlocking {
+    inner: UPSafeCell<MutexBlockingInner>,
+}
+
+struct MutexBlockingInner {
+    locked: bool,
+    fair: bool,
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl MutexBlocking {
+    pub fn new(fair: bool) -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(MutexBlockingInner {
+                    locked: false,
+                    fair,
+                    wait_queue: VecDeque::new(),
+                })
+            },
+        }
+    }
+}
+
+impl Mutex for MutexBlocking {
+    fn lock(&self) {
+        let mut mutex_inner = self.inner.exclusive_access();
+        if !mutex_inner.fair {
+            loop {
+                if !mutex_inner.locked {
+                    mutex_inner.locked = true;
+                    return;
+                }
+                mutex_inner.wait_queue.push_back(current_task().unwrap());
+                drop(mutex_inner);
+                block_current_and_run_next();
+                mutex_inner = self.inner.exclusive_access();
+            }
+        }
+        if mutex_inner.locked {
+            mutex_inner.wait_queue.push_back(current_task().unwrap());
+            drop(mutex_inner);
+            block_current_and_run_next();
+        } else {
+            mutex_inner.locked = true;
+        }
+    }
+
+    fn unlock(&self) -> UnlockOutcome {
+        let mut mutex_inner = self.inner.exclusive_access();
+        assert!(mutex_inner.locked);
+        if mutex_inner.fair {
+            if let Some(waking_task) = mutex_inner.wait_queue.pop_front() {
+                // Direct hand-off: `locked` stays `true` for the successor,
+                // so the mutex is not actually free yet.
+                add_task(Arc::clone(&waking_task));
+                UnlockOutcome::HandedOff(waking_task)
+            } else {
+                mutex_inner.locked = false;
+                UnlockOutcome::Free
+            }
+        } else {
+            mutex_inner.locked = false;
+            if let Some(waking_task) = mutex_inner.wait_queue.pop_front() {
+                add_task(waking_task);
+            }
+            UnlockOutcome::Free
+        }
+    }
+
+    fn lock_timeout(&self) -> bool {
+        let mut mutex_inner = self.inner.exclusive_access();
+        if !mutex_inner.fair {
+            // Unfair: a waiter woken as a retry hint must recheck
+            // `locked` like every other contender, so loop rather than
+            // assume a single wakeup means a grant.
+            loop {
+                if !mutex_inner.locked {
+                    mutex_inner.locked = true;
+                    return true;
+                }
+                let task = current_task().unwrap();
+                mutex_inner.wait_queue.push_back(Arc::clone(&task));
+                drop(mutex_inner);
+                block_current_and_run_next();
+
+                // A grant hint and a timeout both resume us here;
+                // `woken_by_timeout` (set by the timer-expiry path,
+                // cleared by the caller before arming the timer) tells
+                // them apart.
+                let timed_out = task.inner_exclusive_access().woken_by_timeout;
+                if timed_out {
+                    self.inner
+                        .exclusive_access()
+                        .wait_queue
+                        .retain(|t| !Arc::ptr_eq(t, &task));
+                    return false;
+                }
+                mutex_inner = self.inner.exclusive_access();
+            }
+        }
+
+        // Fair: the queue is the only ordering that matters and `unlock`
+        // hands the mutex directly to the waiter it wakes, so a single
+        // wakeup here is trustworthy -- no recheck loop needed.
+        if mutex_inner.locked {
+            let task = current_task().unwrap();
+            mutex_inner.wait_queue.push_back(Arc::clone(&task));
+            drop(mutex_inner);
+            block_current_and_run_next();
+
+            let timed_out = task.inner_exclusive_access().woken_by_timeout;
+            if timed_out {
+                self.inner
+                    .exclusive_access()
+                    .wait_queue
+                    .retain(|t| !Arc::ptr_eq(t, &task));
+            }
+            !timed_out
+        } else {
+            mutex_inner.locked = true;
+            true
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use super::UPSafeCell;
+use crate::task::{add_task, block_current_and_run_next, current_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// A reader/writer lock: any number of readers may hold it concurrently,
+/// but a writer needs exclusive access.
+///
+/// Built on the same blocking-queue machinery as `MutexBlocking`/
+/// `Semaphore`: a reader blocks while a writer holds the lock or a writer
+/// is already queued (so writers can never starve), and a writer blocks
+/// while readers are active or another writer holds it. `writer_priority`
+/// picks which side `write_unlock` drains first when both queues are
+/// non-empty; `read_unlock` always wakes a waiting writer once the last
+/// reader leaves.
+pub struct RwLock {
+    inner: UPSafeCell<RwLockInner>,
+}
+
+struct RwLockInner {
+    readers: usize,
+    writer: bool,
+    writer_priority: bool,
+    waiting_writers: usize,
+    read_wait_queue: VecDeque<Arc<TaskControlBlock>>,
+    write_wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl RwLock {
+    /// Create an unlocked rwlock. `writer_priority` selects whether
+    /// `write_unlock` prefers draining queued writers (`true`) or queued
+    /// readers (`false`) first.
+    pub fn new(writer_priority: bool) -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(RwLockInner {
+                    readers: 0,
+                    writer: false,
+                    writer_priority,
+                    waiting_writers: 0,
+                    read_wait_queue: VecDeque::new(),
+                    write_wait_queue: VecDeque::new(),
+                })
+            },
+        }
+    }
+
+    /// Wake every queued reader, crediting each one's share of `readers`
+    /// before waking it so a woken reader just returns from its block
+    /// point instead of re-checking and double-counting itself.
+    fn drain_readers(inner: &mut RwLockInner) {
+        while let Some(task) = inner.read_wait_queue.pop_front() {
+            inner.readers += 1;
+            add_task(task);
+        }
+    }
+
+    pub fn read_lock(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if inner.writer || inner.waiting_writers > 0 {
+            inner.read_wait_queue.push_back(current_task().unwrap());
+            drop(inner);
+            block_current_and_run_next();
+            return;
+        }
+        inner.readers += 1;
+    }
+
+    pub fn write_lock(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if inner.writer || inner.readers > 0 {
+            inner.waiting_writers += 1;
+            inner.write_wait_queue.push_back(current_task().unwrap());
+            drop(inner);
+            block_current_and_run_next();
+            self.inner.exclusive_access().waiting_writers -= 1;
+            return;
+        }
+        inner.writer = true;
+    }
+
+    pub fn read_unlock(&self) {
+        let mut inner = self.inner.exclusive_access();
+        assert!(inner.readers > 0);
+        inner.readers -= 1;
+        if inner.readers == 0 {
+            if let Some(task) = inner.write_wait_queue.pop_front() {
+                inner.writer = true;
+                add_task(task);
+            }
+        }
+    }
+
+    pub fn write_unlock(&self) {
+        let mut inner = self.inner.exclusive_access();
+        assert!(inner.writer);
+        inner.writer = false;
+        if inner.writer_priority {
+            if let Some(task) = inner.write_wait_queue.pop_front() {
+                inner.writer = true;
+                add_task(task);
+            } else {
+                Self::drain_readers(&mut inner);
+            }
+        } else if !inner.read_wait_queue.is_empty() {
+            Self::drain_readers(&mut inner);
+        } else if let Some(task) = inner.write_wait_queue.pop_front() {
+            inner.writer = true;
+            add_task(task);
+        }
+    }
+}
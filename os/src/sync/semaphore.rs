@@ -0,0 +1,139 @@
+use super::UPSafeCell;
+use crate::task::{add_task, block_current_and_run_next, current_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// A counting semaphore with a selectable fairness mode.
+///
+/// `fair == true` keeps a strict FIFO handoff: a spare unit is only ever
+/// granted through the wait queue, never grabbed out from under an
+/// already-queued waiter, so `up()` always satisfies the longest-waiting
+/// `down()` first (bounded waiting).
+///
+/// `fair == false` (the default, existing fast-path) lets any caller take
+/// a spare unit the instant `count > 0`, even if other tasks are already
+/// queued; a woken waiter loops back and re-checks `count` rather than
+/// assuming it was granted, so a fresh contender can still win the race.
+pub struct Semaphore {
+    inner: UPSafeCell<SemaphoreInner>,
+}
+
+struct SemaphoreInner {
+    count: isize,
+    fair: bool,
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Semaphore {
+    pub fn new(res_count: usize, fair: bool) -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(SemaphoreInner {
+                    count: res_count as isize,
+                    fair,
+                    wait_queue: VecDeque::new(),
+                })
+            },
+        }
+    }
+
+    pub fn up(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if inner.fair {
+            // The unit is handed straight to the queue head rather than
+            // returned to the open pool, so a newcomer can never grab it
+            // ahead of someone already waiting.
+            if let Some(task) = inner.wait_queue.pop_front() {
+                add_task(task);
+            } else {
+                inner.count += 1;
+            }
+        } else {
+            inner.count += 1;
+            if let Some(task) = inner.wait_queue.pop_front() {
+                add_task(task);
+            }
+        }
+    }
+
+    pub fn down(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if inner.fair {
+            if inner.count > 0 && inner.wait_queue.is_empty() {
+                inner.count -= 1;
+                return;
+            }
+            inner.wait_queue.push_back(current_task().unwrap());
+            drop(inner);
+            block_current_and_run_next();
+            return;
+        }
+        loop {
+            if inner.count > 0 {
+                inner.count -= 1;
+                return;
+            }
+            inner.wait_queue.push_back(current_task().unwrap());
+            drop(inner);
+            block_current_and_run_next();
+            inner = self.inner.exclusive_access();
+        }
+    }
+
+    /// Block until a unit is granted, or until woken by the timer the
+    /// caller armed just before this call, whichever happens first.
+    /// Returns whether a unit was actually acquired.
+    pub fn down_timeout(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.fair {
+            // Fair: `up()` hands a unit directly to the queue head, so a
+            // single wakeup here is trustworthy -- no recheck loop needed.
+            if inner.count > 0 && inner.wait_queue.is_empty() {
+                inner.count -= 1;
+                return true;
+            }
+            let task = current_task().unwrap();
+            inner.wait_queue.push_back(Arc::clone(&task));
+            drop(inner);
+            block_current_and_run_next();
+
+            let timed_out = task.inner_exclusive_access().woken_by_timeout;
+            if timed_out {
+                self.inner
+                    .exclusive_access()
+                    .wait_queue
+                    .retain(|t| !Arc::ptr_eq(t, &task));
+            }
+            return !timed_out;
+        }
+
+        // Unfair: a waiter woken as a retry hint must recheck `count`
+        // like every other contender, so loop rather than assume a
+        // single wakeup means a grant.
+        loop {
+            if inner.count > 0 {
+                inner.count -= 1;
+                return true;
+            }
+            let task = current_task().unwrap();
+            inner.wait_queue.push_back(Arc::clone(&task));
+            drop(inner);
+            block_current_and_run_next();
+
+            // A grant hint and a timeout both resume us here;
+            // `woken_by_timeout` tells them apart. On a timeout we may
+            // still be queued -- dequeue ourselves so a spuriously late
+            // `up()` doesn't hand a unit to a waiter that no longer
+            // wants it.
+            let timed_out = task.inner_exclusive_access().woken_by_timeout;
+            if timed_out {
+                self.inner
+                    .exclusive_access()
+                    .wait_queue
+                    .retain(|t| !Arc::ptr_eq(t, &task));
+                return false;
+            }
+            inner = self.inner.exclusive_access();
+        }
+    }
+}
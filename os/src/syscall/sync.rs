@@ -1,8 +1,158 @@
-use crate::sync::{Condvar, Mutex, MutexBlocking, MutexSpin, Semaphore};
-use crate::task::{block_current_and_run_next, current_process, current_task};
+use crate::sync::{Condvar, Mutex, MutexBlocking, MutexSpin, RwLock, Semaphore, UnlockOutcome};
+use crate::task::{block_current_and_run_next, current_process, current_task, ProcessControlBlockInner};
 use crate::timer::{add_timer, get_time_ms};
 use alloc::sync::Arc;
 use alloc::vec;
+
+/// Maximum depth of transitive priority donation, to guard against following
+/// a cycle in the holder/waiter chain.
+const MAX_DONATION_DEPTH: usize = 8;
+
+/// Returned by the timed lock/down syscalls when the wait expires before the
+/// resource is granted.
+const ETIMEDOUT: isize = -110;
+
+/// Who a new waiter on `mutex_id` should actually donate to: the task a
+/// fair-mode unlock already handed the mutex to, if a hand-off is in
+/// flight, or the recorded holder otherwise. `mutex_owner` alone isn't
+/// enough during a hand-off -- it's left pointing at the outgoing holder
+/// until the successor's own `lock()` call returns, which would otherwise
+/// misdirect donation (and its transitive chain-following) at a task
+/// that's done with the mutex and may be blocked on something unrelated.
+fn effective_mutex_owner(process_inner: &ProcessControlBlockInner, mutex_id: usize) -> Option<usize> {
+    process_inner.mutex_pending_successor[mutex_id].or(process_inner.mutex_owner[mutex_id])
+}
+
+/// Donate `waiter_priority` to `holder_tid` if it raises the holder's
+/// effective priority, then follow the holder's own blocking chain (if it is
+/// itself waiting on another mutex) up to `MAX_DONATION_DEPTH` hops.
+fn donate_priority(
+    process_inner: &mut ProcessControlBlockInner,
+    holder_tid: usize,
+    waiter_priority: usize,
+    depth: usize,
+) {
+    if depth == 0 || holder_tid >= process_inner.tasks.len() {
+        return;
+    }
+    let holder = process_inner.get_task(holder_tid);
+    let mut holder_inner = holder.inner_exclusive_access();
+    if waiter_priority <= holder_inner.effective_priority {
+        return;
+    }
+    holder_inner.effective_priority = waiter_priority;
+    let next_holder_tid = holder_inner
+        .blocked_on_mutex
+        .and_then(|mid| effective_mutex_owner(process_inner, mid));
+    drop(holder_inner);
+    if let Some(next_tid) = next_holder_tid {
+        donate_priority(process_inner, next_tid, waiter_priority, depth - 1);
+    }
+}
+
+/// Recompute `tid`'s effective priority as the max of its own base
+/// priority and the highest effective priority among waiters of every
+/// mutex it currently holds. Used both when a holder releases a mutex
+/// (restoring its own priority) and when a donating waiter leaves a wait
+/// early via timeout (restoring the priority of whoever it was donating
+/// to, since that donation no longer applies).
+fn restore_effective_priority(process_inner: &ProcessControlBlockInner, tid: usize) {
+    let task = process_inner.get_task(tid);
+    let mut task_inner = task.inner_exclusive_access();
+    let mut restored_priority = task_inner.base_priority;
+    for (held_mutex_id, &held) in task_inner.m_allocation.iter().enumerate() {
+        if held == 0 {
+            continue;
+        }
+        for &waiter_tid in process_inner.mutex_waiters[held_mutex_id].iter() {
+            let waiter = process_inner.get_task(waiter_tid);
+            let waiter_priority = waiter.inner_exclusive_access().effective_priority;
+            if waiter_priority > restored_priority {
+                restored_priority = waiter_priority;
+            }
+        }
+    }
+    task_inner.effective_priority = restored_priority;
+}
+
+/// A lockable resource identified by kind and id, used to build wait-for
+/// graph edges without caring whether the resource is a mutex or a
+/// semaphore.
+#[derive(Clone, Copy)]
+enum Resource {
+    Mutex(usize),
+    Semaphore(usize),
+}
+
+/// Tasks currently holding `resource` (at most one for a mutex, any number
+/// for a counting semaphore).
+fn resource_holders(process_inner: &ProcessControlBlockInner, resource: Resource) -> Vec<usize> {
+    match resource {
+        Resource::Mutex(id) => process_inner.mutex_owner[id].into_iter().collect(),
+        Resource::Semaphore(id) => process_inner.semaphore_holders[id].clone(),
+    }
+}
+
+/// The resource `tid` is currently blocked waiting for, if any.
+fn resource_awaited_by(process_inner: &ProcessControlBlockInner, tid: usize) -> Option<Resource> {
+    let task = process_inner.get_task(tid);
+    let task_inner = task.inner_exclusive_access();
+    if let Some(mutex_id) = task_inner.blocked_on_mutex {
+        Some(Resource::Mutex(mutex_id))
+    } else {
+        task_inner.blocked_on_sem.map(Resource::Semaphore)
+    }
+}
+
+/// Search the wait-for graph for a cycle that would be created if
+/// `start_tid` were to block on `requested`. Builds edges from a task to
+/// every current holder of the resource it wants, following chains of
+/// already-blocked holders with an iterative DFS and three-color marking
+/// (white/gray/black). Re-entering a gray node means a cycle exists; the
+/// gray stack at that point is the deadlocked set, returned for reporting.
+/// A holder that is the requester itself (recursive re-lock) is skipped
+/// rather than treated as a self-cycle.
+fn find_deadlock_cycle(
+    process_inner: &ProcessControlBlockInner,
+    start_tid: usize,
+    requested: Resource,
+) -> Option<Vec<usize>> {
+    const WHITE: u8 = 0;
+    const GRAY: u8 = 1;
+    const BLACK: u8 = 2;
+
+    let mut color = vec![WHITE; process_inner.tasks.len()];
+    color[start_tid] = GRAY;
+    // Each frame is (tid, holders of the resource it's waiting for, cursor).
+    let mut stack: Vec<(usize, Vec<usize>, usize)> =
+        vec![(start_tid, resource_holders(process_inner, requested), 0)];
+
+    while let Some((tid, holders, idx)) = stack.last_mut() {
+        if *idx >= holders.len() {
+            color[*tid] = BLACK;
+            stack.pop();
+            continue;
+        }
+        let holder_tid = holders[*idx];
+        *idx += 1;
+        if holder_tid == *tid {
+            continue;
+        }
+        match color[holder_tid] {
+            GRAY => return Some(stack.iter().map(|(t, _, _)| *t).collect()),
+            BLACK => continue,
+            _ => {
+                color[holder_tid] = GRAY;
+                let next_holders = match resource_awaited_by(process_inner, holder_tid) {
+                    Some(resource) => resource_holders(process_inner, resource),
+                    None => Vec::new(),
+                };
+                stack.push((holder_tid, next_holders, 0));
+            }
+        }
+    }
+    None
+}
 /// sleep syscall
 pub fn sys_sleep(ms: usize) -> isize {
     trace!(
@@ -23,7 +173,13 @@ pub fn sys_sleep(ms: usize) -> isize {
     0
 }
 /// mutex create syscall
-pub fn sys_mutex_create(blocking: bool) -> isize {
+///
+/// `fair` selects FIFO bounded-waiting mode for a blocking mutex: waiters
+/// join a strict FIFO queue and the resource is handed directly to the
+/// queue head on unlock, instead of being released to open contention.
+/// Ignored for a spinlock mutex (`blocking == false`); the default
+/// (`fair == false`) keeps the existing unfair fast-path.
+pub fn sys_mutex_create(blocking: bool, fair: bool) -> isize {
     trace!(
         "kernel:pid[{}] tid[{}] sys_mutex_create",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -39,7 +195,7 @@ pub fn sys_mutex_create(blocking: bool) -> isize {
     let mutex: Option<Arc<dyn Mutex>> = if !blocking {
         Some(Arc::new(MutexSpin::new()))
     } else {
-        Some(Arc::new(MutexBlocking::new()))
+        Some(Arc::new(MutexBlocking::new(fair)))
     };
     let mut process_inner = process.inner_exclusive_access();
     let res: usize;
@@ -54,7 +210,6 @@ pub fn sys_mutex_create(blocking: bool) -> isize {
         res = id;
     } else {
         process_inner.mutex_list.push(mutex);
-        // 更新可分配Sync资源
         let id: usize = process_inner.mutex_list.len() - 1;
         res = id;
     }
@@ -62,6 +217,29 @@ pub fn sys_mutex_create(blocking: bool) -> isize {
     // 更新可分配Sync资源
     process_inner.adjust_m_available(res, 1);
 
+    // `mutex_owner`/`mutex_waiters` are indexed by mutex id just like
+    // `mutex_list`, but nothing grows them when a slot is freshly pushed --
+    // and a reused (freed) slot may still carry a stale owner/waiters from
+    // whatever mutex previously lived at `res`, which would misdirect
+    // priority donation and the wait-for graph onto the wrong holder.
+    if res >= process_inner.mutex_owner.len() {
+        process_inner.mutex_owner.push(None);
+        process_inner.mutex_waiters.push(Vec::new());
+        process_inner.mutex_pending_successor.push(None);
+    } else {
+        process_inner.mutex_owner[res] = None;
+        process_inner.mutex_waiters[res].clear();
+        process_inner.mutex_pending_successor[res] = None;
+    }
+    // A fair-mode mutex leans on `mutex_owner`/`mutex_waiters` exactly the
+    // same way an unfair one does (the FIFO hand-off lives entirely inside
+    // `MutexBlocking` itself), so this invariant has to hold regardless of
+    // `fair` -- cheap enough to assert here rather than find out from a
+    // panicking index somewhere downstream.
+    debug_assert_eq!(process_inner.mutex_list.len(), process_inner.mutex_owner.len());
+    debug_assert_eq!(process_inner.mutex_list.len(), process_inner.mutex_waiters.len());
+    debug_assert_eq!(process_inner.mutex_list.len(), process_inner.mutex_pending_successor.len());
+
     for task_id in 0..process_inner.tasks.len() {
         let task = process_inner.get_task(task_id);
         let mut task_inner = task.inner_exclusive_access();
@@ -138,21 +316,69 @@ pub fn sys_mutex_lock(mutex_id: usize) -> isize {
                 return -0xDEAD;
             }
         }
+    } else if process_inner.use_cycle_detection {
+        let task = current_task().unwrap();
+        let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
+        drop(task);
+        if let Some(cycle) = find_deadlock_cycle(&process_inner, tid, Resource::Mutex(mutex_id)) {
+            trace!(
+                "kernel: deadlock detected: mutex {} would complete cycle {:?}",
+                mutex_id, cycle
+            );
+            let task = current_task().unwrap();
+            let mut task_inner = task.inner_exclusive_access();
+            task_inner.m_need[mutex_id] -= 1;
+            return -0xDEAD;
+        }
     }
 
     let task = current_task().unwrap();
+    let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
     let mut task_inner = task.inner_exclusive_access();
 
     task_inner.m_need[mutex_id] -= 1;
     task_inner.m_allocation[mutex_id] += 1;
     process_inner.m_available[mutex_id] -= 1;
 
+    // Priority donation: if someone else is already holding this mutex and
+    // has a lower effective priority than us, bump theirs (transitively) so
+    // they can run, finish, and hand the mutex back to us sooner. The
+    // waiter stays recorded in `mutex_waiters` and `mutex_owner` keeps
+    // pointing at the real holder until `mutex.lock()` below actually
+    // returns -- clearing either one here, before we've truly acquired the
+    // lock, would make `sys_mutex_unlock`'s priority-restore scan blind to
+    // us and would hand a second contender's donation to the wrong task.
+    if let Some(holder_tid) = effective_mutex_owner(&process_inner, mutex_id) {
+        task_inner.blocked_on_mutex = Some(mutex_id);
+        let waiter_priority = task_inner.effective_priority;
+        process_inner.mutex_waiters[mutex_id].push(tid);
+        drop(task_inner);
+        donate_priority(&mut process_inner, holder_tid, waiter_priority, MAX_DONATION_DEPTH);
+    } else {
+        task_inner.blocked_on_mutex = None;
+        process_inner.mutex_owner[mutex_id] = Some(tid);
+        drop(task_inner);
+    }
+
     let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
     drop(process_inner);
     drop(process);
-    drop(task_inner);
-    drop(task);
     mutex.lock();
+
+    // `mutex.lock()` only returns once we actually hold the mutex: this is
+    // the point where we stop being a waiter and become the real owner. A
+    // task that took the uncontended branch above is already its own
+    // owner, so the retain/owner-write here are harmless no-ops for it.
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.blocked_on_mutex = None;
+    process_inner.mutex_waiters[mutex_id].retain(|&w| w != tid);
+    process_inner.mutex_owner[mutex_id] = Some(tid);
+    if process_inner.mutex_pending_successor[mutex_id] == Some(tid) {
+        process_inner.mutex_pending_successor[mutex_id] = None;
+    }
     0
 }
 /// mutex unlock syscall
@@ -173,21 +399,224 @@ pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
     let mut process_inner = process.inner_exclusive_access();
 
     let task = current_task().unwrap();
+    let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
     let mut task_inner = task.inner_exclusive_access();
 
     process_inner.m_available[mutex_id] += 1;
     task_inner.m_allocation[mutex_id] -= 1;
+    drop(task_inner);
+
+    // Restore our effective priority: it's the max of our own base priority
+    // and the highest effective priority among waiters of every mutex we
+    // still hold (donations we picked up for mutexes we've since released
+    // no longer apply once this one is gone).
+    restore_effective_priority(&process_inner, tid);
 
     let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
     drop(process_inner);
     drop(process);
-    drop(task_inner);
     drop(task);
-    mutex.unlock();
+
+    // A fair-mode unlock hands the mutex straight to the queue head and
+    // keeps it logically locked for that hand-off, so `mutex_owner` must
+    // keep pointing at the outgoing holder until the successor actually
+    // wakes and claims it in `sys_mutex_lock`/`sys_mutex_timedlock`.
+    // Clearing it here unconditionally would let a third task racing in
+    // that window see a "free" mutex, skip priority donation, and record
+    // itself as owner despite `mutex.lock()` still blocking it -- corrupting
+    // both the donation target and the wait-for graph until the real
+    // successor finally runs. Only clear it when the primitive is actually
+    // free; while a hand-off is pending, record the real successor in
+    // `mutex_pending_successor` so a new contender donates to it instead of
+    // the now-irrelevant outgoing holder.
+    match mutex.unlock() {
+        UnlockOutcome::Free => {
+            let process = current_process();
+            let mut process_inner = process.inner_exclusive_access();
+            process_inner.mutex_owner[mutex_id] = None;
+        }
+        UnlockOutcome::HandedOff(successor) => {
+            let successor_tid = successor.inner_exclusive_access().res.as_ref().unwrap().tid;
+            let process = current_process();
+            let mut process_inner = process.inner_exclusive_access();
+            process_inner.mutex_pending_successor[mutex_id] = Some(successor_tid);
+        }
+    }
     0
 }
+/// mutex timed lock syscall
+///
+/// Like `sys_mutex_lock`, but gives up after `ms` milliseconds instead of
+/// blocking forever, returning `ETIMEDOUT` in that case. Goes through the
+/// same `m_need`/`m_allocation`/`m_available` bookkeeping and deadlock
+/// checks as `sys_mutex_lock`, and touches `mutex_owner`/`mutex_waiters`
+/// the same way. The timer is only armed once we know the mutex is
+/// actually contended and we're about to join its wait queue -- an
+/// uncontended call acquires immediately and never registers a timer, so
+/// it can't spuriously wake itself with `woken_by_timeout` set later on.
+pub fn sys_mutex_timedlock(mutex_id: usize, ms: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_mutex_timedlock",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.adjust_m_need(mutex_id, 1);
+
+    drop(task_inner);
+    drop(task);
+
+    if process_inner.use_dead_lock {
+        let mut work = process_inner.m_available.clone();
+        let task_len = process_inner.tasks.len();
+        let mut finish = vec![false; task_len];
+
+        loop {
+            let mut found = false;
+
+            for task_id in 0..task_len {
+                if finish[task_id] {
+                    continue;
+                }
+
+                let task = process_inner.get_task(task_id);
+                let mut task_inner = task.inner_exclusive_access();
+
+                let needs_adjustment = work.iter().enumerate().any(|(mutex_id, &mutex_remain)| {
+                    task_inner.adjust_m_need(mutex_id, 0);
+                    task_inner.m_need[mutex_id] > mutex_remain
+                });
+
+                if !needs_adjustment {
+                    finish[task_id] = true;
+                    work.iter_mut().enumerate().for_each(|(pos, ptr)| {
+                        task_inner.adjust_m_allocation(pos, 0);
+                        *ptr += task_inner.m_allocation[pos];
+                    });
+                    found = true;
+                }
+            }
+
+            if !found {
+                break;
+            }
+        }
+
+        let task = current_task().unwrap();
+        let mut task_inner = task.inner_exclusive_access();
+        if finish.iter().any(|x| *x == false) {
+            task_inner.m_need[mutex_id] -= 1;
+            return -0xDEAD;
+        }
+    } else if process_inner.use_cycle_detection {
+        let task = current_task().unwrap();
+        let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
+        drop(task);
+        if let Some(cycle) = find_deadlock_cycle(&process_inner, tid, Resource::Mutex(mutex_id)) {
+            trace!(
+                "kernel: deadlock detected: mutex {} would complete cycle {:?}",
+                mutex_id, cycle
+            );
+            let task = current_task().unwrap();
+            let mut task_inner = task.inner_exclusive_access();
+            task_inner.m_need[mutex_id] -= 1;
+            return -0xDEAD;
+        }
+    }
+
+    let task = current_task().unwrap();
+    let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
+    let mut task_inner = task.inner_exclusive_access();
+
+    task_inner.m_need[mutex_id] -= 1;
+    task_inner.m_allocation[mutex_id] += 1;
+    process_inner.m_available[mutex_id] -= 1;
+
+    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+
+    if process_inner.mutex_owner[mutex_id].is_some() {
+        // Contended: join the wait queue and only now arm the timer, at
+        // the moment we actually start waiting. Donate the same way
+        // `sys_mutex_lock` does, since a timed waiter is just as entitled
+        // to boost a lower-priority holder -- it just gives up on its own
+        // deadline instead of waiting forever.
+        task_inner.blocked_on_mutex = Some(mutex_id);
+        let waiter_priority = task_inner.effective_priority;
+        process_inner.mutex_waiters[mutex_id].push(tid);
+        drop(task_inner);
+        if let Some(holder_tid) = effective_mutex_owner(&process_inner, mutex_id) {
+            donate_priority(&mut process_inner, holder_tid, waiter_priority, MAX_DONATION_DEPTH);
+        }
+        drop(process_inner);
+        drop(process);
+
+        let task = current_task().unwrap();
+        task.inner_exclusive_access().woken_by_timeout = false;
+        add_timer(get_time_ms() + ms, Arc::clone(&task));
+        drop(task);
+
+        let acquired = mutex.lock_timeout();
+
+        let process = current_process();
+        let mut process_inner = process.inner_exclusive_access();
+        let task = current_task().unwrap();
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.blocked_on_mutex = None;
+        // This wait is resolved, whichever way -- bump our own generation
+        // so the armed timer becomes stale and `check_timer` drops it
+        // unfired even if it hasn't popped yet (e.g. we won the race on a
+        // real grant just before the deadline).
+        task_inner.timer_generation = task_inner.timer_generation.wrapping_add(1);
+        process_inner.mutex_waiters[mutex_id].retain(|&w| w != tid);
+
+        if acquired {
+            process_inner.mutex_owner[mutex_id] = Some(tid);
+            if process_inner.mutex_pending_successor[mutex_id] == Some(tid) {
+                process_inner.mutex_pending_successor[mutex_id] = None;
+            }
+            0
+        } else {
+            task_inner.m_allocation[mutex_id] -= 1;
+            process_inner.m_available[mutex_id] += 1;
+            drop(task_inner);
+            // We were donating our priority to whoever held this mutex;
+            // giving up now without restoring them would leave that
+            // donation in place indefinitely, since `sys_mutex_unlock`
+            // only restores priority for waiters still in `mutex_waiters`
+            // (we just removed ourselves from it above).
+            if let Some(holder_tid) = effective_mutex_owner(&process_inner, mutex_id) {
+                restore_effective_priority(&process_inner, holder_tid);
+            }
+            ETIMEDOUT
+        }
+    } else {
+        // Uncontended: acquire directly, no timer ever needed.
+        task_inner.blocked_on_mutex = None;
+        process_inner.mutex_owner[mutex_id] = Some(tid);
+        drop(task_inner);
+        drop(process_inner);
+        drop(process);
+        mutex.lock();
+        0
+    }
+}
 /// semaphore create syscall
-pub fn sys_semaphore_create(res_count: usize) -> isize {
+///
+/// `fair` selects FIFO mode: waiters join a strict FIFO queue and `up()`
+/// hands its unit directly to the queue head, so it always satisfies the
+/// longest-waiting `down()` first, giving a bounded-waiting guarantee. The default (`fair == false`) keeps the
+/// existing unfair fast-path.
+pub fn sys_semaphore_create(res_count: usize, fair: bool) -> isize {
     trace!(
         "kernel:pid[{}] tid[{}] sys_semaphore_create",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -208,16 +637,29 @@ pub fn sys_semaphore_create(res_count: usize) -> isize {
         .find(|(_, item)| item.is_none())
         .map(|(id, _)| id)
     {
-        process_inner.semaphore_list[id] = Some(Arc::new(Semaphore::new(res_count)));
+        process_inner.semaphore_list[id] = Some(Arc::new(Semaphore::new(res_count, fair)));
         id
     } else {
         process_inner
             .semaphore_list
-            .push(Some(Arc::new(Semaphore::new(res_count))));
+            .push(Some(Arc::new(Semaphore::new(res_count, fair))));
         process_inner.semaphore_list.len() - 1
     };
     // 更新可分配Sync资源
     process_inner.adjust_s_available(id, res_count);
+
+    // `semaphore_holders` is indexed by semaphore id just like
+    // `semaphore_list`, but nothing grows it on a freshly pushed slot, and
+    // a reused (freed) slot may still carry holders left over from whatever
+    // semaphore previously lived at `id` -- which `find_deadlock_cycle`
+    // would then treat as holding the new semaphore.
+    if id >= process_inner.semaphore_holders.len() {
+        process_inner.semaphore_holders.push(Vec::new());
+    } else {
+        process_inner.semaphore_holders[id].clear();
+    }
+    debug_assert_eq!(process_inner.semaphore_list.len(), process_inner.semaphore_holders.len());
+
     for task_id in 0..process_inner.tasks.len() {
         let task = process_inner.get_task(task_id);
         let mut task_inner = task.inner_exclusive_access();
@@ -249,6 +691,19 @@ pub fn sys_semaphore_up(sem_id: usize) -> isize {
     let mut task_inner = task.inner_exclusive_access();
 
     task_inner.s_allocation[sem_id] -= 1;
+    let tid = task_inner.res.as_ref().unwrap().tid;
+    // The releaser is no longer a holder of the unit it just gave up, so
+    // drop one matching entry from the wait-for graph as soon as up() is
+    // called, before any other task can observe it. A task holding several
+    // units of the same semaphore pushed one `tid` per down(), so removing
+    // every occurrence here would erase it from the graph after releasing
+    // just one -- only the first matching entry is removed.
+    if let Some(pos) = process_inner.semaphore_holders[sem_id]
+        .iter()
+        .position(|&holder| holder == tid)
+    {
+        process_inner.semaphore_holders[sem_id].remove(pos);
+    }
 
     drop(process_inner);
     drop(task_inner);
@@ -324,15 +779,35 @@ pub fn sys_semaphore_down(sem_id: usize) -> isize {
             task_inner.s_need[sem_id] -= 1;
             return -0xDEAD;
         }
+    } else if process_inner.use_cycle_detection && process_inner.s_available[sem_id] == 0 {
+        let task = current_task().unwrap();
+        let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
+        drop(task);
+        if let Some(cycle) = find_deadlock_cycle(&process_inner, tid, Resource::Semaphore(sem_id)) {
+            trace!(
+                "kernel: deadlock detected: semaphore {} would complete cycle {:?}",
+                sem_id, cycle
+            );
+            let task = current_task().unwrap();
+            let mut task_inner = task.inner_exclusive_access();
+            task_inner.s_need[sem_id] -= 1;
+            return -0xDEAD;
+        }
     }
 
     let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
+    let tid = task_inner.res.as_ref().unwrap().tid;
 
-    if process_inner.s_available[sem_id] > 0 {
+    let was_contended = process_inner.s_available[sem_id] == 0;
+    if !was_contended {
         task_inner.s_need[sem_id] -= 1;
         task_inner.adjust_s_allocation(sem_id, 1);
         process_inner.s_available[sem_id] -= 1;
+        task_inner.blocked_on_sem = None;
+        process_inner.semaphore_holders[sem_id].push(tid);
+    } else {
+        task_inner.blocked_on_sem = Some(sem_id);
     }
 
     let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
@@ -341,6 +816,291 @@ pub fn sys_semaphore_down(sem_id: usize) -> isize {
     drop(task_inner);
     drop(task);
     sem.down();
+
+    // `sem.down()` only returns once a unit is actually granted. For the
+    // uncontended branch above we're already recorded as a holder and fully
+    // accounted; a contended waiter only becomes one here, once it truly
+    // wakes with a unit in hand, and needs the exact same `s_need`/
+    // `s_allocation`/`s_available` bookkeeping the uncontended branch did up
+    // front -- otherwise it would sit in `semaphore_holders` forever wrong
+    // (never added) while `blocked_on_sem` never clears, leaving a phantom
+    // wait-for edge that hides the real multi-hop chain from
+    // `find_deadlock_cycle`, and the next `sys_semaphore_up` for this id
+    // would underflow `s_allocation` that was never incremented.
+    if was_contended {
+        let process = current_process();
+        let mut process_inner = process.inner_exclusive_access();
+        let task = current_task().unwrap();
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.blocked_on_sem = None;
+        task_inner.s_need[sem_id] -= 1;
+        task_inner.adjust_s_allocation(sem_id, 1);
+        process_inner.s_available[sem_id] -= 1;
+        process_inner.semaphore_holders[sem_id].push(tid);
+    }
+    0
+}
+/// semaphore timed down syscall
+///
+/// Like `sys_semaphore_down`, but gives up after `ms` milliseconds instead
+/// of blocking forever, returning `ETIMEDOUT` in that case. Goes through
+/// the same `s_need`/`s_allocation`/`s_available` bookkeeping, deadlock
+/// checks, and `semaphore_holders`/`blocked_on_sem` tracking as
+/// `sys_semaphore_down`. The timer is only armed once we know the
+/// semaphore is actually exhausted and we're about to join its wait
+/// queue, so an immediately-granted down() never leaves a stray timer
+/// that would fire later and spuriously wake the task.
+pub fn sys_semaphore_timeddown(sem_id: usize, ms: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_semaphore_timeddown",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.adjust_s_need(sem_id, 1);
+
+    drop(task_inner);
+    drop(task);
+
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+
+    if process_inner.use_dead_lock {
+        let mut work = process_inner.s_available.clone();
+        let task_len = process_inner.tasks.len();
+        let mut finish = vec![false; task_len];
+
+        loop {
+            let mut found = false;
+
+            for task_id in 0..task_len {
+                if finish[task_id] {
+                    continue;
+                }
+
+                let task = process_inner.get_task(task_id);
+                let mut task_inner = task.inner_exclusive_access();
+
+                let can_proceed = !work.iter().enumerate().any(|(sem_id, &sem_remain)| {
+                    task_inner.adjust_s_need(sem_id, 0);
+                    task_inner.s_need[sem_id] > sem_remain
+                });
+
+                if can_proceed {
+                    finish[task_id] = true;
+                    work.iter_mut().enumerate().for_each(|(pos, ptr)| {
+                        task_inner.adjust_s_allocation(pos, 0);
+                        *ptr += task_inner.s_allocation[pos];
+                    });
+                    found = true;
+                }
+            }
+
+            if !found {
+                break;
+            }
+        }
+
+        let task = current_task().unwrap();
+        let mut task_inner = task.inner_exclusive_access();
+        if finish.iter().any(|x| *x == false) {
+            task_inner.s_need[sem_id] -= 1;
+            return -0xDEAD;
+        }
+    } else if process_inner.use_cycle_detection && process_inner.s_available[sem_id] == 0 {
+        let task = current_task().unwrap();
+        let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
+        drop(task);
+        if let Some(cycle) = find_deadlock_cycle(&process_inner, tid, Resource::Semaphore(sem_id)) {
+            trace!(
+                "kernel: deadlock detected: semaphore {} would complete cycle {:?}",
+                sem_id, cycle
+            );
+            let task = current_task().unwrap();
+            let mut task_inner = task.inner_exclusive_access();
+            task_inner.s_need[sem_id] -= 1;
+            return -0xDEAD;
+        }
+    }
+
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let tid = task_inner.res.as_ref().unwrap().tid;
+
+    let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
+
+    if process_inner.s_available[sem_id] > 0 {
+        // Uncontended: acquire directly, no timer ever needed.
+        task_inner.s_need[sem_id] -= 1;
+        task_inner.adjust_s_allocation(sem_id, 1);
+        process_inner.s_available[sem_id] -= 1;
+        task_inner.blocked_on_sem = None;
+        process_inner.semaphore_holders[sem_id].push(tid);
+        drop(task_inner);
+        drop(process_inner);
+        drop(process);
+        sem.down();
+        0
+    } else {
+        // Contended: join the wait queue and only now arm the timer, at
+        // the moment we actually start waiting.
+        task_inner.blocked_on_sem = Some(sem_id);
+        drop(task_inner);
+        drop(process_inner);
+        drop(process);
+
+        task.inner_exclusive_access().woken_by_timeout = false;
+        add_timer(get_time_ms() + ms, Arc::clone(&task));
+        drop(task);
+
+        let acquired = sem.down_timeout();
+
+        let process = current_process();
+        let mut process_inner = process.inner_exclusive_access();
+        let task = current_task().unwrap();
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.blocked_on_sem = None;
+        // This wait is resolved, whichever way -- bump our own generation
+        // so the armed timer becomes stale and `check_timer` drops it
+        // unfired even if it hasn't popped yet (e.g. we won the race on a
+        // real grant just before the deadline).
+        task_inner.timer_generation = task_inner.timer_generation.wrapping_add(1);
+
+        if acquired {
+            task_inner.s_need[sem_id] -= 1;
+            task_inner.adjust_s_allocation(sem_id, 1);
+            process_inner.s_available[sem_id] -= 1;
+            process_inner.semaphore_holders[sem_id].push(tid);
+            0
+        } else {
+            task_inner.s_need[sem_id] -= 1;
+            ETIMEDOUT
+        }
+    }
+}
+/// rwlock create syscall
+///
+/// `writer_priority` selects the fairness mode: when true, a draining writer
+/// is preferred over queued readers once the lock goes free; when false,
+/// readers are drained first. Either way, a reader that arrives while a
+/// writer is queued or holding the lock still blocks, so writers can never
+/// starve.
+pub fn sys_rwlock_create(writer_priority: bool) -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_rwlock_create",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let id = if let Some(id) = process_inner
+        .rwlock_list
+        .iter()
+        .enumerate()
+        .find(|(_, item)| item.is_none())
+        .map(|(id, _)| id)
+    {
+        process_inner.rwlock_list[id] = Some(Arc::new(RwLock::new(writer_priority)));
+        id
+    } else {
+        process_inner
+            .rwlock_list
+            .push(Some(Arc::new(RwLock::new(writer_priority))));
+        process_inner.rwlock_list.len() - 1
+    };
+    id as isize
+}
+/// rwlock read lock syscall
+pub fn sys_rwlock_read_lock(rwlock_id: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_rwlock_read_lock",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let rwlock = Arc::clone(process_inner.rwlock_list[rwlock_id].as_ref().unwrap());
+    drop(process_inner);
+    rwlock.read_lock();
+    0
+}
+/// rwlock write lock syscall
+pub fn sys_rwlock_write_lock(rwlock_id: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_rwlock_write_lock",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let rwlock = Arc::clone(process_inner.rwlock_list[rwlock_id].as_ref().unwrap());
+    drop(process_inner);
+    rwlock.write_lock();
+    0
+}
+/// rwlock read unlock syscall
+pub fn sys_rwlock_read_unlock(rwlock_id: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_rwlock_read_unlock",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let rwlock = Arc::clone(process_inner.rwlock_list[rwlock_id].as_ref().unwrap());
+    drop(process_inner);
+    rwlock.read_unlock();
+    0
+}
+/// rwlock write unlock syscall
+pub fn sys_rwlock_write_unlock(rwlock_id: usize) -> isize {
+    trace!(
+        "kernel:pid[{}] tid[{}] sys_rwlock_write_unlock",
+        current_task().unwrap().process.upgrade().unwrap().getpid(),
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+    );
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let rwlock = Arc::clone(process_inner.rwlock_list[rwlock_id].as_ref().unwrap());
+    drop(process_inner);
+    rwlock.write_unlock();
     0
 }
 /// condvar create syscall
@@ -418,12 +1178,28 @@ pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
 }
 /// enable deadlock detection syscall
 ///
-/// YOUR JOB: Implement deadlock detection, but might not all in this syscall
-pub fn sys_enable_deadlock_detect(_enabled: usize) -> isize {
-    trace!("kernel: sys_enable_deadlock_detect NOT IMPLEMENTED");
-    match _enabled {
-        1 => current_process().inner_exclusive_access().use_dead_lock = true,
-        0 => current_process().inner_exclusive_access().use_dead_lock = false,
+/// `mode` selects how `sys_mutex_lock`/`sys_semaphore_down` guard against
+/// deadlock: `0` disables checking, `1` keeps the existing banker's-style
+/// safe-state avoidance, `2` switches to wait-for-graph cycle detection,
+/// which only runs at the moment a task would actually block and reports
+/// the real cycle instead of conservatively refusing safe states.
+pub fn sys_enable_deadlock_detect(mode: usize) -> isize {
+    trace!("kernel: sys_enable_deadlock_detect mode={}", mode);
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    match mode {
+        0 => {
+            process_inner.use_dead_lock = false;
+            process_inner.use_cycle_detection = false;
+        }
+        1 => {
+            process_inner.use_dead_lock = true;
+            process_inner.use_cycle_detection = false;
+        }
+        2 => {
+            process_inner.use_dead_lock = false;
+            process_inner.use_cycle_detection = true;
+        }
         _ => return -1,
     };
     0
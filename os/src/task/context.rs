@@ -0,0 +1,38 @@
+//! The callee-saved register set `__switch` swaps across a context switch
+
+use crate::trap::trap_return;
+
+/// Everything `__switch` needs to resume a task: its saved `ra`/`sp` and the
+/// callee-saved `s0`-`s11` registers. Caller-saved registers don't need to
+/// be here -- the trap handler already spilled them to the trap frame
+/// before a task ever reaches `__switch`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TaskContext {
+    ra: usize,
+    sp: usize,
+    s: [usize; 12],
+}
+
+impl TaskContext {
+    /// A context with no meaningful state, used only to seed the per-core
+    /// idle loop's own context slot before it ever switches away.
+    pub fn zero_init() -> Self {
+        Self {
+            ra: 0,
+            sp: 0,
+            s: [0; 12],
+        }
+    }
+
+    /// A context for a task that has never run: its first `__switch` into
+    /// it "returns" straight into `trap_return`, landing it in user space
+    /// at the entry point its trap frame was set up with.
+    pub fn goto_trap_return(kstack_ptr: usize) -> Self {
+        Self {
+            ra: trap_return as usize,
+            sp: kstack_ptr,
+            s: [0; 12],
+        }
+    }
+}
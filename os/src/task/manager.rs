@@ -0,0 +1,52 @@
+//! The ready queue and task dispatch
+
+use super::task::TaskControlBlock;
+use crate::sync::UPSafeCell;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
+
+pub struct TaskManager {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl TaskManager {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+
+    pub fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+
+    /// Pick the ready task with the highest `effective_priority`, ties
+    /// broken in FIFO order (the longest-queued of the tied tasks wins).
+    /// A plain `pop_front` would make priority donation inert -- raising
+    /// a number that run order never consults -- so the donated priority
+    /// has to be what selection actually sorts on.
+    pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let (best_idx, _) = self
+            .ready_queue
+            .iter()
+            .enumerate()
+            .max_by_key(|(idx, task)| {
+                let priority = task.inner_exclusive_access().effective_priority;
+                (priority, core::cmp::Reverse(*idx))
+            })?;
+        self.ready_queue.remove(best_idx)
+    }
+}
+
+lazy_static! {
+    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> = unsafe { UPSafeCell::new(TaskManager::new()) };
+}
+
+pub fn add_task(task: Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().add(task);
+}
+
+pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
+    TASK_MANAGER.exclusive_access().fetch()
+}
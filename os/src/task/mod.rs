@@ -0,0 +1,27 @@
+//! Task/process management: scheduling, the process table, and the
+//! priority/blocking/banker's-algorithm state the sync syscalls operate on.
+
+mod context;
+mod manager;
+mod process;
+mod processor;
+mod switch;
+mod task;
+
+pub use manager::add_task;
+pub use process::{ProcessControlBlock, ProcessControlBlockInner};
+pub use processor::{
+    block_current_and_run_next, current_process, current_task, run_tasks,
+    suspend_current_and_run_next, take_current_task,
+};
+pub use task::{TaskControlBlock, TaskControlBlockInner, TaskUserRes};
+
+/// Where a task is in its lifecycle. `TaskManager`/`Processor` use this to
+/// decide whether it belongs in the ready queue, is the one currently
+/// running, or is parked waiting on something else to re-queue it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TaskStatus {
+    Ready,
+    Running,
+    Blocked,
+}
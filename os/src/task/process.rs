@@ -0,0 +1,126 @@
+//! Process control block: the resources shared by every thread in a process
+
+use super::task::TaskControlBlock;
+use crate::sync::{Condvar, Mutex, RwLock, Semaphore, UPSafeCell};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::RefMut;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_PID: AtomicUsize = AtomicUsize::new(0);
+
+fn alloc_pid() -> usize {
+    NEXT_PID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A process: an address space plus the thread group and sync-object
+/// tables its threads share. `pid` is fixed for the process's lifetime;
+/// everything that can change lives in `ProcessControlBlockInner`.
+pub struct ProcessControlBlock {
+    pub pid: usize,
+    inner: UPSafeCell<ProcessControlBlockInner>,
+}
+
+pub struct ProcessControlBlockInner {
+    pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
+
+    pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
+    pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
+    pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    pub rwlock_list: Vec<Option<Arc<RwLock>>>,
+
+    /// Remaining, unallocated units of each mutex (0 or 1)/semaphore,
+    /// as the banker's algorithm sees them.
+    pub m_available: Vec<usize>,
+    pub s_available: Vec<usize>,
+
+    /// `1` selects the existing banker's-algorithm safe-state check in
+    /// `sys_mutex_lock`/`sys_semaphore_down`; `0` disables checking. Mutually
+    /// exclusive with `use_cycle_detection` -- `sys_enable_deadlock_detect`
+    /// only ever turns one of the two on at a time.
+    pub use_dead_lock: bool,
+    /// Switches `sys_mutex_lock`/`sys_semaphore_down` to wait-for-graph
+    /// cycle detection instead: only runs at the moment a task would
+    /// actually block, and reports the real cycle rather than
+    /// conservatively refusing every potentially-unsafe state.
+    pub use_cycle_detection: bool,
+
+    /// The task currently holding each mutex, if any. Grown/reset
+    /// alongside `mutex_list` by `sys_mutex_create`, and is what lets
+    /// `donate_priority` find who to donate to and `find_deadlock_cycle`
+    /// build wait-for edges for a mutex.
+    pub mutex_owner: Vec<Option<usize>>,
+    /// Tasks queued on each mutex, in join order. Used both to restore a
+    /// releasing task's effective priority (the max over everyone still
+    /// waiting on mutexes it holds) and, like `mutex_owner`, to walk the
+    /// wait-for graph.
+    pub mutex_waiters: Vec<Vec<usize>>,
+    /// The task a fair-mode unlock has directly handed a mutex to, while
+    /// `mutex_owner` still points at the outgoing holder pending that
+    /// successor's own `lock()` call returning. A new contender donates to
+    /// this task instead of the outgoing holder whenever it's set; cleared
+    /// once the successor actually claims `mutex_owner` for itself.
+    pub mutex_pending_successor: Vec<Option<usize>>,
+    /// Tasks currently holding at least one unit of each semaphore. Grown/
+    /// reset alongside `semaphore_list` by `sys_semaphore_create`, and is
+    /// `find_deadlock_cycle`'s semaphore counterpart to `mutex_owner`.
+    pub semaphore_holders: Vec<Vec<usize>>,
+}
+
+impl ProcessControlBlockInner {
+    pub fn get_task(&self, tid: usize) -> Arc<TaskControlBlock> {
+        self.tasks[tid].as_ref().unwrap().clone()
+    }
+
+    pub fn adjust_m_available(&mut self, id: usize, delta: usize) {
+        if id >= self.m_available.len() {
+            self.m_available.resize(id + 1, 0);
+        }
+        self.m_available[id] += delta;
+    }
+
+    pub fn adjust_s_available(&mut self, id: usize, delta: usize) {
+        if id >= self.s_available.len() {
+            self.s_available.resize(id + 1, 0);
+        }
+        self.s_available[id] += delta;
+    }
+}
+
+impl ProcessControlBlock {
+    pub fn getpid(&self) -> usize {
+        self.pid
+    }
+
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, ProcessControlBlockInner> {
+        self.inner.exclusive_access()
+    }
+
+    /// A process with no threads yet and every sync-object table empty --
+    /// used both for a brand new process and, since nothing here is
+    /// inherited across `fork`, for a forked child: mutexes, semaphores,
+    /// and their owner/waiter bookkeeping are local to a process and start
+    /// fresh rather than being copied from the parent.
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            pid: alloc_pid(),
+            inner: unsafe {
+                UPSafeCell::new(ProcessControlBlockInner {
+                    tasks: Vec::new(),
+                    mutex_list: Vec::new(),
+                    semaphore_list: Vec::new(),
+                    condvar_list: Vec::new(),
+                    rwlock_list: Vec::new(),
+                    m_available: Vec::new(),
+                    s_available: Vec::new(),
+                    use_dead_lock: false,
+                    use_cycle_detection: false,
+                    mutex_owner: Vec::new(),
+                    mutex_waiters: Vec::new(),
+                    mutex_pending_successor: Vec::new(),
+                    semaphore_holders: Vec::new(),
+                })
+            },
+        })
+    }
+}
@@ -0,0 +1,110 @@
+//! The single running-task slot and the scheduling loop that fills it
+
+use super::context::TaskContext;
+use super::manager::{add_task, fetch_task};
+use super::process::ProcessControlBlock;
+use super::switch::__switch;
+use super::task::TaskControlBlock;
+use super::TaskStatus;
+use crate::sync::UPSafeCell;
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
+
+pub struct Processor {
+    current: Option<Arc<TaskControlBlock>>,
+    idle_task_cx: TaskContext,
+}
+
+impl Processor {
+    pub fn new() -> Self {
+        Self {
+            current: None,
+            idle_task_cx: TaskContext::zero_init(),
+        }
+    }
+
+    fn idle_task_cx_ptr(&mut self) -> *mut TaskContext {
+        &mut self.idle_task_cx as *mut _
+    }
+
+    pub fn take_current(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.current.take()
+    }
+
+    pub fn current(&self) -> Option<Arc<TaskControlBlock>> {
+        self.current.as_ref().cloned()
+    }
+}
+
+lazy_static! {
+    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+}
+
+pub fn current_task() -> Option<Arc<TaskControlBlock>> {
+    PROCESSOR.exclusive_access().current()
+}
+
+pub fn current_process() -> Arc<ProcessControlBlock> {
+    current_task().unwrap().process.upgrade().unwrap()
+}
+
+pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
+    PROCESSOR.exclusive_access().take_current()
+}
+
+/// The per-core idle loop: fetch the highest-`effective_priority` ready
+/// task from the manager and switch into it, returning here only once
+/// that task blocks, yields, or exits.
+pub fn run_tasks() {
+    loop {
+        let mut processor = PROCESSOR.exclusive_access();
+        if let Some(task) = fetch_task() {
+            let idle_task_cx_ptr = processor.idle_task_cx_ptr();
+            let next_task_cx_ptr = {
+                let mut task_inner = task.inner_exclusive_access();
+                task_inner.task_status = TaskStatus::Running;
+                &task_inner.task_cx as *const TaskContext
+            };
+            processor.current = Some(task);
+            drop(processor);
+            unsafe {
+                __switch(idle_task_cx_ptr, next_task_cx_ptr);
+            }
+        }
+    }
+}
+
+/// Suspend the current task -- put it back on the ready queue -- and
+/// return control to the scheduling loop.
+pub fn suspend_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let task_cx_ptr = {
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.task_status = TaskStatus::Ready;
+        &mut task_inner.task_cx as *mut TaskContext
+    };
+    add_task(task);
+    schedule(task_cx_ptr);
+}
+
+/// Block the current task and return control to the scheduling loop. The
+/// caller is responsible for making sure something will re-queue it later
+/// (a mutex/semaphore/rwlock wait queue, or a timer).
+pub fn block_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let task_cx_ptr = {
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.task_status = TaskStatus::Blocked;
+        &mut task_inner.task_cx as *mut TaskContext
+    };
+    schedule(task_cx_ptr);
+}
+
+fn schedule(switched_task_cx_ptr: *mut TaskContext) {
+    let mut processor = PROCESSOR.exclusive_access();
+    let idle_task_cx_ptr = processor.idle_task_cx_ptr();
+    drop(processor);
+    unsafe {
+        __switch(switched_task_cx_ptr, idle_task_cx_ptr);
+    }
+}
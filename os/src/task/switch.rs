@@ -0,0 +1,14 @@
+//! Raw context switch between two `TaskContext`s
+
+use super::context::TaskContext;
+use core::arch::global_asm;
+
+global_asm!(include_str!("switch.S"));
+
+extern "C" {
+    /// Save the caller's register state into `*current_task_cx_ptr`, load
+    /// `*next_task_cx_ptr`'s, and return into whatever `ra` that points to.
+    /// Never returns through its own call site -- the next return happens
+    /// when some other task switches back into `current_task_cx_ptr`.
+    pub fn __switch(current_task_cx_ptr: *mut TaskContext, next_task_cx_ptr: *const TaskContext);
+}
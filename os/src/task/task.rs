@@ -0,0 +1,145 @@
+//! Task (thread) control block
+
+use super::context::TaskContext;
+use super::process::ProcessControlBlock;
+use super::TaskStatus;
+use crate::sync::UPSafeCell;
+use alloc::sync::Weak;
+use alloc::vec::Vec;
+use core::cell::RefMut;
+
+/// The user-mode resources owned by one thread within its process: its
+/// kernel-visible id and where its user stack lives in the process's
+/// address space.
+pub struct TaskUserRes {
+    pub tid: usize,
+    pub ustack_base: usize,
+    pub process: Weak<ProcessControlBlock>,
+}
+
+/// One schedulable thread.
+///
+/// `process` is the only field read without going through
+/// `inner_exclusive_access()` -- it never changes after the task is
+/// created, unlike everything in `TaskControlBlockInner`, which covers
+/// status, priority, blocking state, and the banker's-algorithm/
+/// wait-for-graph bookkeeping the sync syscalls maintain.
+pub struct TaskControlBlock {
+    pub process: Weak<ProcessControlBlock>,
+    inner: UPSafeCell<TaskControlBlockInner>,
+}
+
+pub struct TaskControlBlockInner {
+    pub res: Option<TaskUserRes>,
+    pub task_cx: TaskContext,
+    pub task_status: TaskStatus,
+    pub exit_code: Option<i32>,
+
+    /// This task's own priority, fixed at spawn time and what
+    /// `effective_priority` is restored to once every mutex it donated
+    /// through has been released.
+    pub base_priority: usize,
+    /// `base_priority`, or higher while this task holds a mutex that a
+    /// higher-priority task is blocked waiting for (priority inheritance).
+    pub effective_priority: usize,
+    /// The mutex this task is currently blocked trying to lock, if any.
+    pub blocked_on_mutex: Option<usize>,
+    /// The semaphore this task is currently blocked trying to down, if
+    /// any. Tracked the same way as `blocked_on_mutex`, and read by
+    /// `resource_awaited_by` to build the wait-for graph's edges out of a
+    /// blocked task.
+    pub blocked_on_sem: Option<usize>,
+    /// Set by `crate::timer::check_timer` once this task's deadline has
+    /// passed; only meaningful right after a timed wait wakes this task
+    /// back up, to tell a real grant apart from a timeout in
+    /// `MutexBlocking::lock_timeout`/`Semaphore::down_timeout`.
+    pub woken_by_timeout: bool,
+    /// Bumped every time a timed wait this task was in ends, however it
+    /// ends. `crate::timer::add_timer` tags the timer it arms with the
+    /// generation at that moment; `check_timer` drops any timer whose
+    /// tagged generation no longer matches, so a wait resolved early by a
+    /// real grant can't have its stale timer fire later and corrupt an
+    /// unrelated, later wait.
+    pub timer_generation: usize,
+
+    /// Banker's-algorithm bookkeeping, indexed by mutex/semaphore id: how
+    /// many units this task currently holds, and how many more it has
+    /// requested but not yet been granted.
+    pub m_allocation: Vec<usize>,
+    pub m_need: Vec<usize>,
+    pub s_allocation: Vec<usize>,
+    pub s_need: Vec<usize>,
+}
+
+impl TaskControlBlockInner {
+    /// Grow `m_need` to cover `id` if it doesn't already (a freshly
+    /// created mutex id this task has never touched), then add `delta`.
+    pub fn adjust_m_need(&mut self, id: usize, delta: usize) {
+        if id >= self.m_need.len() {
+            self.m_need.resize(id + 1, 0);
+        }
+        self.m_need[id] += delta;
+    }
+
+    pub fn adjust_m_allocation(&mut self, id: usize, delta: usize) {
+        if id >= self.m_allocation.len() {
+            self.m_allocation.resize(id + 1, 0);
+        }
+        self.m_allocation[id] += delta;
+    }
+
+    pub fn adjust_s_need(&mut self, id: usize, delta: usize) {
+        if id >= self.s_need.len() {
+            self.s_need.resize(id + 1, 0);
+        }
+        self.s_need[id] += delta;
+    }
+
+    pub fn adjust_s_allocation(&mut self, id: usize, delta: usize) {
+        if id >= self.s_allocation.len() {
+            self.s_allocation.resize(id + 1, 0);
+        }
+        self.s_allocation[id] += delta;
+    }
+}
+
+impl TaskControlBlock {
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
+        self.inner.exclusive_access()
+    }
+
+    /// Create a new thread in `process`, already given its `res` and a
+    /// kernel stack to run on. Used both for a process's first thread and
+    /// for every later `thread_create`/fork -- the banker's-algorithm
+    /// vectors start empty and grow lazily via `adjust_*` the first time a
+    /// mutex/semaphore id touches this task, and the priority/blocking
+    /// fields all start at their neutral, unblocked values.
+    pub fn new(
+        process: Weak<ProcessControlBlock>,
+        res: TaskUserRes,
+        kstack_ptr: usize,
+        base_priority: usize,
+    ) -> Self {
+        Self {
+            process,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    res: Some(res),
+                    task_cx: TaskContext::goto_trap_return(kstack_ptr),
+                    task_status: TaskStatus::Ready,
+                    exit_code: None,
+                    base_priority,
+                    effective_priority: base_priority,
+                    blocked_on_mutex: None,
+                    blocked_on_sem: None,
+                    woken_by_timeout: false,
+                    timer_generation: 0,
+                    m_allocation: Vec::new(),
+                    m_need: Vec::new(),
+                    s_allocation: Vec::new(),
+                    s_need: Vec::new(),
+                })
+            },
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Wall-clock timers: `sys_sleep` and timed lock/down wake-ups
+
+use crate::config::CLOCK_FREQ;
+use crate::sbi::set_timer;
+use crate::sync::UPSafeCell;
+use crate::task::{add_task, TaskControlBlock};
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use core::cmp::Ordering;
+use lazy_static::lazy_static;
+use riscv::register::time;
+
+const TICKS_PER_SEC: usize = 100;
+const MSEC_PER_SEC: usize = 1000;
+
+pub fn get_time() -> usize {
+    time::read()
+}
+
+pub fn get_time_ms() -> usize {
+    time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
+}
+
+pub fn set_next_trigger() {
+    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+}
+
+/// One pending wake-up: `task` should be made ready again once `expire_ms`
+/// has passed, unless its wait was already resolved early -- `generation`
+/// is `task`'s `timer_generation` at the moment this timer was armed, and
+/// `check_timer` drops the timer unfired if that no longer matches.
+struct TimerCondVar {
+    expire_ms: usize,
+    task: Arc<TaskControlBlock>,
+    generation: usize,
+}
+
+impl PartialEq for TimerCondVar {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire_ms == other.expire_ms
+    }
+}
+impl Eq for TimerCondVar {}
+impl PartialOrd for TimerCondVar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for TimerCondVar {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // `BinaryHeap` is a max-heap; reverse so the earliest deadline is
+        // always the one on top.
+        other.expire_ms.cmp(&self.expire_ms)
+    }
+}
+
+lazy_static! {
+    static ref TIMERS: UPSafeCell<BinaryHeap<TimerCondVar>> = unsafe { UPSafeCell::new(BinaryHeap::new()) };
+}
+
+pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
+    let generation = task.inner_exclusive_access().timer_generation;
+    TIMERS.exclusive_access().push(TimerCondVar {
+        expire_ms,
+        task,
+        generation,
+    });
+}
+
+/// Called on every timer interrupt: wake every task whose deadline has
+/// passed.
+///
+/// `sys_sleep`'s waiter just resumes. A `sys_mutex_timedlock`/
+/// `sys_semaphore_timeddown` waiter is woken the exact same way -- via
+/// `add_task`, same as a real grant would wake it -- but with
+/// `woken_by_timeout` set first, which is what lets
+/// `MutexBlocking::lock_timeout`/`Semaphore::down_timeout` tell the two
+/// apart and, on a timeout, remove the task from the primitive's own wait
+/// queue themselves.
+///
+/// A timer whose `generation` no longer matches the task's current
+/// `timer_generation` belongs to a wait that was already resolved early
+/// (a real grant beat the deadline) -- firing it anyway would re-enqueue
+/// a task that's already running or blocked on something else entirely,
+/// so it's dropped unfired instead.
+pub fn check_timer() {
+    let current_ms = get_time_ms();
+    let mut timers = TIMERS.exclusive_access();
+    while let Some(timer) = timers.peek() {
+        if timer.expire_ms > current_ms {
+            break;
+        }
+        let timer = timers.pop().unwrap();
+        let mut task_inner = timer.task.inner_exclusive_access();
+        if task_inner.timer_generation != timer.generation {
+            continue;
+        }
+        task_inner.timer_generation = task_inner.timer_generation.wrapping_add(1);
+        task_inner.woken_by_timeout = true;
+        drop(task_inner);
+        add_task(timer.task);
+    }
+}